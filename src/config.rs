@@ -0,0 +1,54 @@
+use std::io;
+use std::path::PathBuf;
+
+/// Runtime options for how the server resolves and exposes files on disk.
+///
+/// Built from CLI args and environment variables in [`ServerConfig::from_env`]
+/// and shared read-only across the worker pool.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub document_root: PathBuf,
+    pub show_index: bool,
+    pub follow_symlinks: bool,
+}
+
+impl ServerConfig {
+    /// Parses `--root <path>`, `--no-index` and `--no-follow-symlinks` from `args`,
+    /// falling back to the `DOCUMENT_ROOT`, `SHOW_INDEX` and `FOLLOW_SYMLINKS` env
+    /// vars, then to the current directory with listing and symlinks both enabled.
+    pub fn from_env(args: &[String]) -> io::Result<ServerConfig> {
+        let mut document_root = match std::env::var("DOCUMENT_ROOT") {
+            Ok(root) => PathBuf::from(root),
+            Err(_) => std::env::current_dir()?,
+        };
+        let mut show_index = env_flag("SHOW_INDEX", true);
+        let mut follow_symlinks = env_flag("FOLLOW_SYMLINKS", true);
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--root" => {
+                    if let Some(path) = iter.next() {
+                        document_root = PathBuf::from(path);
+                    }
+                }
+                "--no-index" => show_index = false,
+                "--no-follow-symlinks" => follow_symlinks = false,
+                _ => {}
+            }
+        }
+
+        Ok(ServerConfig {
+            document_root,
+            show_index,
+            follow_symlinks,
+        })
+    }
+}
+
+fn env_flag(name: &str, default: bool) -> bool {
+    match std::env::var(name) {
+        Ok(value) => !matches!(value.as_str(), "0" | "false"),
+        Err(_) => default,
+    }
+}
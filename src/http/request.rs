@@ -0,0 +1,135 @@
+use super::response::HttpResponse;
+use crate::config::ServerConfig;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    V1_0,
+    V1_1,
+    V2_0,
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            Version::V1_0 => "HTTP/1.0",
+            Version::V1_1 => "HTTP/1.1",
+            Version::V2_0 => "HTTP/2.0",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct Resource {
+    pub path: String,
+}
+
+/// A parsed `Range: bytes=...` request header.
+///
+/// `Suffix(n)` means "the last n bytes", matching the `bytes=-500` form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentRange {
+    From(usize),
+    Full(usize, usize),
+    Suffix(usize),
+}
+
+impl ContentRange {
+    fn parse(value: &str) -> Option<ContentRange> {
+        let spec = value.trim().strip_prefix("bytes=")?;
+        let (start, end) = spec.split_once('-')?;
+        let start = start.trim();
+        let end = end.trim();
+
+        if start.is_empty() {
+            Some(ContentRange::Suffix(end.parse().ok()?))
+        } else if end.is_empty() {
+            Some(ContentRange::From(start.parse().ok()?))
+        } else {
+            Some(ContentRange::Full(start.parse().ok()?, end.parse().ok()?))
+        }
+    }
+}
+
+/// A request's headers, keyed by lower-cased header name so lookups don't
+/// care about the casing a particular client sent.
+#[derive(Debug, Clone, Default)]
+pub struct Headers(HashMap<String, String>);
+
+impl Headers {
+    fn parse<'a>(lines: impl Iterator<Item = &'a str>) -> Headers {
+        let mut headers = HashMap::new();
+        for line in lines {
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+        }
+        Headers(headers)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub version: Version,
+    pub resource: Resource,
+    pub headers: Headers,
+    pub range: Option<ContentRange>,
+    pub if_modified_since: Option<String>,
+    pub if_none_match: Option<String>,
+    pub accept_encoding: Option<String>,
+}
+
+impl HttpRequest {
+    pub fn new(raw: &str) -> io::Result<HttpRequest> {
+        let mut lines = raw.lines();
+        let request_line = lines.next().unwrap_or_default();
+        let mut parts = request_line.split_whitespace();
+
+        let method = match parts.next().unwrap_or("GET") {
+            "GET" => Method::Get,
+            "HEAD" => Method::Head,
+            _ => Method::Other,
+        };
+        let path = parts.next().unwrap_or("/").to_string();
+        let version = Version::V2_0;
+
+        let headers = Headers::parse(lines);
+        let range = headers.get("range").and_then(ContentRange::parse);
+        let if_modified_since = headers.get("if-modified-since").map(str::to_string);
+        let if_none_match = headers.get("if-none-match").map(str::to_string);
+        let accept_encoding = headers.get("accept-encoding").map(str::to_string);
+
+        Ok(HttpRequest {
+            method,
+            version,
+            resource: Resource { path },
+            headers,
+            range,
+            if_modified_since,
+            if_none_match,
+            accept_encoding,
+        })
+    }
+
+    pub fn response(&self, config: &ServerConfig) -> io::Result<HttpResponse> {
+        HttpResponse::new(self, config)
+    }
+}
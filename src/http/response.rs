@@ -1,14 +1,66 @@
-use super::request::HttpRequest;
+use super::request::{ContentRange, HttpRequest, Method};
 use super::request::Version;
+use crate::config::ServerConfig;
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Compression;
 use std::fmt::Display;
+use std::fs::File;
 use std::io;
-use std::path::{Path, PathBuf};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
 use infer;
 use mime_guess::from_path;
 use url_escape::decode;
-use log::{error, warn};
+use log::error;
 use walkdir::WalkDir;
 
+/// Index files probed for, in order, when a directory is requested.
+const INDEX_FILES: [&str; 3] = ["index.html", "index.htm", "index.txt"];
+
+/// Bodies smaller than this aren't worth the CPU cost of compressing.
+const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// On-disk files at or under this size are buffered into memory so they can
+/// be compressed like generated pages; larger files keep streaming straight
+/// from disk, uncompressed, to stay memory-bounded.
+const COMPRESSIBLE_FILE_LIMIT: u64 = 2 * 1024 * 1024;
+
+/// Whether a MIME type is worth compressing (text-ish formats; images and
+/// archives are already compressed and would only grow).
+fn is_compressible(content_type: &str) -> bool {
+    let base = content_type.split(';').next().unwrap_or(content_type).trim();
+    base.starts_with("text/") || matches!(base, "application/json" | "image/svg+xml")
+}
+
+/// Picks the best encoding this server supports from a client's
+/// `Accept-Encoding` header, preferring gzip over deflate.
+fn negotiate_encoding(accept_encoding: Option<&str>) -> Option<&'static str> {
+    let accept_encoding = accept_encoding?;
+    if accept_encoding.contains("gzip") {
+        Some("gzip")
+    } else if accept_encoding.contains("deflate") {
+        Some("deflate")
+    } else {
+        None
+    }
+}
+
+fn compress_body(body: &[u8], encoding: &str) -> io::Result<Vec<u8>> {
+    match encoding {
+        "gzip" => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body)?;
+            encoder.finish()
+        }
+        "deflate" => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body)?;
+            encoder.finish()
+        }
+        _ => Ok(body.to_vec()),
+    }
+}
+
 #[derive(Debug)]
 pub struct HttpResponse {
     pub version: Version,
@@ -16,24 +68,220 @@ pub struct HttpResponse {
     pub content_length: usize,
     pub content_type: String,
     pub accept_ranges: AcceptRanges,
-    pub response_body: Vec<u8>,
+    pub content_range: Option<String>,
+    pub last_modified: Option<String>,
+    pub etag: Option<String>,
+    pub allow: Option<String>,
+    pub content_encoding: Option<String>,
+    pub vary: Option<String>,
+    pub response_body: ResponseBody,
     pub current_path: String,
 }
 
+/// The body `handle_client` writes to the stream after the header block.
+///
+/// Files are served from disk in bounded chunks rather than loaded fully
+/// into memory, so a multi-gigabyte download doesn't OOM a worker thread.
+#[derive(Debug)]
+pub enum ResponseBody {
+    Memory(Vec<u8>),
+    File { file: File, start: u64, length: u64 },
+}
+
+/// Weak `ETag` derived from a file's length and modification time.
+fn weak_etag(len: u64, modified: std::time::SystemTime) -> String {
+    let mtime_secs = modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    format!("W/\"{}-{}\"", len, mtime_secs)
+}
+
+/// Whether the client's cached copy is still fresh per `If-None-Match` /
+/// `If-Modified-Since`, in which case the response should be a bare 304.
+fn is_not_modified(request: &HttpRequest, etag: &str, modified: std::time::SystemTime) -> bool {
+    if let Some(if_none_match) = &request.if_none_match {
+        return if_none_match == etag;
+    }
+    if let Some(if_modified_since) = &request.if_modified_since {
+        if let Ok(since) = httpdate::parse_http_date(if_modified_since) {
+            // `parse_http_date` floors to whole seconds, so floor `modified`
+            // the same way before comparing, matching `weak_etag` above.
+            let modified_secs = modified
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let since_secs = since
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            return modified_secs <= since_secs;
+        }
+    }
+    false
+}
+
+/// Resolves a parsed `Range` header against the total size of the resource.
+///
+/// Returns the inclusive `(start, end)` byte bounds to serve, or `None` if the
+/// range is unsatisfiable (e.g. `start` is past the end of the resource).
+fn resolve_range(range: ContentRange, total: usize) -> Option<(usize, usize)> {
+    if total == 0 {
+        return None;
+    }
+
+    let (start, end) = match range {
+        ContentRange::From(start) => (start, total - 1),
+        ContentRange::Full(start, end) => (start, end.min(total - 1)),
+        ContentRange::Suffix(suffix) => {
+            let suffix = suffix.min(total);
+            (total - suffix, total - 1)
+        }
+    };
+
+    if start > end || start >= total {
+        None
+    } else {
+        Some((start, end))
+    }
+}
+
+/// The pieces of an `HttpResponse` that depend only on the file being served,
+/// shared between a directly-requested file and a resolved directory index.
+struct FilePayload {
+    status: ResponseStatus,
+    content_type: String,
+    content_length: usize,
+    content_range: Option<String>,
+    last_modified: Option<String>,
+    etag: Option<String>,
+    response_body: ResponseBody,
+}
+
+fn build_file_response(path: &Path, request: &HttpRequest) -> io::Result<FilePayload> {
+    let metadata = std::fs::metadata(path)?;
+    let total = metadata.len() as usize;
+    let modified = metadata.modified()?;
+    let file_etag = weak_etag(metadata.len(), modified);
+    let last_modified = Some(httpdate::fmt_http_date(modified));
+    let etag = Some(file_etag.clone());
+
+    if is_not_modified(request, &file_etag, modified) {
+        return Ok(FilePayload {
+            status: ResponseStatus::NotModified,
+            content_type: "text/plain".to_string(),
+            content_length: 0,
+            content_range: None,
+            last_modified,
+            etag,
+            response_body: ResponseBody::Memory(Vec::new()),
+        });
+    }
+
+    // Detect content type using infer, sniffing only the file's header
+    let content_type = if let Some(kind) = infer::get_from_path(path)? {
+        kind.mime_type().to_string()
+    } else {
+        // Fallback to mime_guess
+        from_path(path).first_or_octet_stream().to_string()
+    };
+
+    let mut status = ResponseStatus::OK;
+    let mut content_range = None;
+    let mut start = 0usize;
+    let mut length = total;
+
+    if let Some(range) = request.range {
+        match resolve_range(range, total) {
+            Some((range_start, range_end)) => {
+                status = ResponseStatus::PartialContent;
+                content_range = Some(format!("bytes {}-{}/{}", range_start, range_end, total));
+                start = range_start;
+                length = range_end - range_start + 1;
+            }
+            None => {
+                status = ResponseStatus::RangeNotSatisfiable;
+                content_range = Some(format!("bytes */{}", total));
+                length = 0;
+            }
+        }
+    }
+
+    Ok(FilePayload {
+        status,
+        content_type,
+        content_length: length,
+        content_range,
+        last_modified,
+        etag,
+        response_body: ResponseBody::File {
+            file: File::open(path)?,
+            start: start as u64,
+            length: length as u64,
+        },
+    })
+}
+
 impl HttpResponse {
-    pub fn new(request: &HttpRequest) -> io::Result<HttpResponse> {
+    pub fn new(request: &HttpRequest, config: &ServerConfig) -> io::Result<HttpResponse> {
         let version = Version::V2_0;
         let mut status: ResponseStatus = ResponseStatus::NotFound;
         let mut content_length: usize = 0;
         let mut content_type = "text/plain".to_string();
         let mut accept_ranges: AcceptRanges = AcceptRanges::None;
         let current_path = request.resource.path.clone();
-        let mut response_body = Vec::new();
+        let mut response_body = ResponseBody::Memory(Vec::new());
+        let mut last_modified: Option<String> = None;
+        let mut etag: Option<String> = None;
 
-        let rootcwd = std::env::current_dir()?.canonicalize()?;
+        if matches!(request.method, Method::Other) {
+            return Ok(HttpResponse {
+                version,
+                status: ResponseStatus::MethodNotAllowed,
+                content_length: 0,
+                content_type,
+                accept_ranges,
+                content_range: None,
+                last_modified,
+                etag,
+                allow: Some("GET, HEAD".to_string()),
+                content_encoding: None,
+                vary: None,
+                response_body,
+                current_path,
+            });
+        }
+
+        let rootcwd = config.document_root.canonicalize()?;
         let decoded_path = decode(&request.resource.path).into_owned();
         let resource_path = Path::new(&decoded_path);
-        let resource = rootcwd.join(&resource_path).canonicalize()?;
+        let joined = rootcwd.join(&resource_path);
+        let mut content_range: Option<String> = None;
+
+        if !config.follow_symlinks {
+            // Walk every path component, not just the leaf, so a symlink in
+            // an intermediate directory can't be used to sidestep the check.
+            let mut current = std::path::PathBuf::new();
+            for component in joined.components() {
+                match component {
+                    std::path::Component::ParentDir => {
+                        current.pop();
+                    }
+                    std::path::Component::CurDir => {}
+                    part => {
+                        current.push(part);
+                        if let Ok(meta) = std::fs::symlink_metadata(&current) {
+                            if meta.file_type().is_symlink() {
+                                error!("Symlink access denied: {:?}", current);
+                                return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Symlink access denied"));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let resource = joined.canonicalize()?;
 
         // Ensure the new path is within the server root directory
         if !resource.starts_with(&rootcwd) {
@@ -42,75 +290,143 @@ impl HttpResponse {
 
         if resource.exists() {
             if resource.is_file() {
-                let content = std::fs::read(&resource)?;
-                content_length = content.len();
-                status = ResponseStatus::OK;
                 accept_ranges = AcceptRanges::Bytes;
+                let payload = build_file_response(&resource, request)?;
+                status = payload.status;
+                content_type = payload.content_type;
+                content_length = payload.content_length;
+                content_range = payload.content_range;
+                last_modified = payload.last_modified;
+                etag = payload.etag;
+                response_body = payload.response_body;
+            } else if resource.is_dir() {
+                let index_file = INDEX_FILES
+                    .iter()
+                    .map(|name| resource.join(name))
+                    .find(|candidate| candidate.is_file());
 
-                // Detect content type using infer
-                if let Some(kind) = infer::get(&content) {
-                    content_type = kind.mime_type().to_string();
+                if let Some(index_file) = index_file {
+                    accept_ranges = AcceptRanges::Bytes;
+                    let payload = build_file_response(&index_file, request)?;
+                    status = payload.status;
+                    content_type = payload.content_type;
+                    content_length = payload.content_length;
+                    content_range = payload.content_range;
+                    last_modified = payload.last_modified;
+                    etag = payload.etag;
+                    response_body = payload.response_body;
+                } else if !config.show_index {
+                    status = ResponseStatus::Forbidden;
+                    content_type = "text/html".to_string();
+                    let forbidden = "<html>\n<body>\n<h1>403 Forbidden</h1>\n</body>\n</html>\n";
+                    content_length = forbidden.len();
+                    response_body = ResponseBody::Memory(forbidden.as_bytes().to_vec());
                 } else {
-                    // Fallback to mime_guess
-                    content_type = from_path(&resource).first_or_octet_stream().to_string();
-                }
-
-                response_body = content;
-            } else if resource.is_dir() {
-                // Handle directory listing or navigation
-                let mut begin_html = r#"
-<!DOCTYPE html> 
-<html> 
-<head> 
-    <meta charset="utf-8"> 
+                    // Handle directory listing or navigation
+                    let mut begin_html = r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
     <style>
         body { font-family: Arial, sans-serif; }
         a { text-decoration: none; color: blue; }
         a:hover { text-decoration: underline; }
     </style>
-</head> 
+</head>
 <body>"#.to_string();
 
-                let header = format!("<h1>Currently in {}</h1>", resource.to_string_lossy());
+                    let header = format!("<h1>Currently in {}</h1>", resource.to_string_lossy());
 
-                let mut dir_listing = String::new();
-                if let Some(parent) = resource.parent() {
-                    let parent_link = parent.strip_prefix(&rootcwd).unwrap_or(parent).to_str().unwrap_or("..");
-                    dir_listing.push_str(&format!("<a href=\"{}\">..</a><br>", parent_link));
-                }
+                    let mut dir_listing = String::new();
+                    if let Some(parent) = resource.parent() {
+                        let parent_link = parent.strip_prefix(&rootcwd).unwrap_or(parent).to_str().unwrap_or("..");
+                        dir_listing.push_str(&format!("<a href=\"{}\">..</a><br>", parent_link));
+                    }
 
-                for entry in WalkDir::new(&resource).max_depth(1).min_depth(1) {
-                    let entry = entry?;
-                    let path = entry.path();
-                    let display = path.file_name().unwrap_or_default().to_string_lossy().to_string();
-                    let link = path.strip_prefix(&rootcwd).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?.to_str().unwrap_or(&display);
-                    let link = format!("/{}", link.trim_start_matches('/')); // Ensure the link is correctly constructed
-                    dir_listing.push_str(&format!("<a href=\"{}\">{}</a><br>", html_escape::encode_text(&link), html_escape::encode_text(&display)));
-                }
+                    for entry in WalkDir::new(&resource).max_depth(1).min_depth(1) {
+                        let entry = entry?;
+                        let path = entry.path();
+                        let display = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                        let link = path.strip_prefix(&rootcwd).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?.to_str().unwrap_or(&display);
+                        let link = format!("/{}", link.trim_start_matches('/')); // Ensure the link is correctly constructed
+                        dir_listing.push_str(&format!("<a href=\"{}\">{}</a><br>", html_escape::encode_text(&link), html_escape::encode_text(&display)));
+                    }
 
-                content_length = dir_listing.len();
-                status = ResponseStatus::OK;
-                content_type = "text/html".to_string();
+                    status = ResponseStatus::OK;
+                    content_type = "text/html".to_string();
 
-                let end_html = r#"
+                    let end_html = r#"
 </body>
 </html>"#.to_string();
 
-                let content = format!(
-                    "{}{}{}{}",
-                    begin_html, header, dir_listing, end_html
-                );
-                response_body = content.into_bytes();
+                    let content = format!(
+                        "{}{}{}{}",
+                        begin_html, header, dir_listing, end_html
+                    );
+                    content_length = content.len();
+                    response_body = ResponseBody::Memory(content.into_bytes());
+                }
             }
         } else {
             error!("Path does not exist: {:?}", resource);
             let four_o_four = "<html>\n<body>\n<h1>404 Not Found</h1>\n</body>\n</html>\n";
+            content_type = "text/html".to_string();
             content_length = four_o_four.len();
-            let content = format!(
-                "{} {}\r\n{}\r\nContent-Length: {}\r\nContent-Type: text/html\r\n\r\n{}",
-                version, status, accept_ranges, content_length, four_o_four
-            );
-            response_body = content.into_bytes();
+            response_body = ResponseBody::Memory(four_o_four.as_bytes().to_vec());
+        }
+
+        // Small compressible files on disk are buffered into memory so they
+        // can be gzipped below like generated pages. Larger files keep
+        // streaming straight from disk, uncompressed, to stay memory-bounded,
+        // and ranged/partial bodies are left alone so their byte offsets stay
+        // meaningful.
+        let should_buffer_file = matches!(
+            &response_body,
+            ResponseBody::File { length, .. }
+                if status == ResponseStatus::OK
+                    && content_range.is_none()
+                    && *length <= COMPRESSIBLE_FILE_LIMIT
+                    && is_compressible(&content_type)
+        );
+        if should_buffer_file {
+            if let ResponseBody::File { mut file, start, length } =
+                std::mem::replace(&mut response_body, ResponseBody::Memory(Vec::new()))
+            {
+                file.seek(SeekFrom::Start(start))?;
+                let mut buf = vec![0u8; length as usize];
+                file.read_exact(&mut buf)?;
+                response_body = ResponseBody::Memory(buf);
+            }
+        }
+
+        // Transparently compress compressible, in-memory 200 OK bodies
+        // (generated listings and small files buffered above); error pages
+        // are sent uncompressed.
+        let mut content_encoding: Option<String> = None;
+        let mut vary: Option<String> = None;
+        let body_in_memory = matches!(response_body, ResponseBody::Memory(_));
+        if status == ResponseStatus::OK && content_range.is_none() && body_in_memory && is_compressible(&content_type) {
+            if let ResponseBody::Memory(body) = std::mem::replace(&mut response_body, ResponseBody::Memory(Vec::new())) {
+                vary = Some("Accept-Encoding".to_string());
+                if body.len() >= COMPRESSION_THRESHOLD {
+                    if let Some(encoding) = negotiate_encoding(request.accept_encoding.as_deref()) {
+                        let compressed = compress_body(&body, encoding)?;
+                        content_length = compressed.len();
+                        content_encoding = Some(encoding.to_string());
+                        response_body = ResponseBody::Memory(compressed);
+                    } else {
+                        response_body = ResponseBody::Memory(body);
+                    }
+                } else {
+                    response_body = ResponseBody::Memory(body);
+                }
+            }
+        }
+
+        // HEAD computes headers exactly as GET but carries no body.
+        if matches!(request.method, Method::Head) {
+            response_body = ResponseBody::Memory(Vec::new());
         }
 
         Ok(HttpResponse {
@@ -119,30 +435,46 @@ impl HttpResponse {
             content_length,
             content_type,
             accept_ranges,
+            content_range,
+            last_modified,
+            etag,
+            allow: None,
+            content_encoding,
+            vary,
             response_body,
             current_path,
         })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum ResponseStatus {
     OK = 200,
+    PartialContent = 206,
+    NotModified = 304,
+    Forbidden = 403,
     NotFound = 404,
+    MethodNotAllowed = 405,
+    RangeNotSatisfiable = 416,
 }
 
 impl Display for ResponseStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let msg = match self {
             ResponseStatus::OK => "200 OK",
+            ResponseStatus::PartialContent => "206 PARTIAL CONTENT",
+            ResponseStatus::NotModified => "304 NOT MODIFIED",
+            ResponseStatus::Forbidden => "403 FORBIDDEN",
             ResponseStatus::NotFound => "404 NOT FOUND",
+            ResponseStatus::MethodNotAllowed => "405 METHOD NOT ALLOWED",
+            ResponseStatus::RangeNotSatisfiable => "416 RANGE NOT SATISFIABLE",
         };
         write!(f, "{}", msg)
     }
 }
 
 #[derive(Debug)]
-enum AcceptRanges {
+pub enum AcceptRanges {
     Bytes,
     None,
 }
@@ -150,8 +482,8 @@ enum AcceptRanges {
 impl Display for AcceptRanges {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let msg = match self {
-            AcceptRanges::Bytes => "accept-ranges: bytes",
-            AcceptRanges::None => "accept-ranges: none",
+            AcceptRanges::Bytes => "bytes",
+            AcceptRanges::None => "none",
         };
         write!(f, "{}", msg)
     }
@@ -1,40 +1,110 @@
 use log::{debug, error, info};
 use std::{
-    io::{self, Read, Write},
+    io::{self, Read, Seek, SeekFrom, Write},
     net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
     sync::Arc,
 };
+use simple_http::config::ServerConfig;
 use simple_http::http::request;
+use simple_http::http::response::{AcceptRanges, ResponseBody, ResponseStatus};
 use threadpool::ThreadPool;
 
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Header blocks larger than this are rejected rather than buffered forever.
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+
 fn create_socket() -> SocketAddr {
     SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::LOCALHOST), 5500)
 }
 
-fn handle_client(mut stream: TcpStream) -> io::Result<()> {
-    let mut buffer = vec![0; 4096];
-    stream.read(&mut buffer)?;
+/// Reads from `stream` until the CRLF-CRLF header terminator is seen, so
+/// headers split across TCP segments (or exceeding a single read) aren't
+/// silently truncated.
+fn read_request_headers(stream: &mut TcpStream) -> io::Result<String> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    loop {
+        if buffer.windows(4).any(|window| window == b"\r\n\r\n") {
+            break;
+        }
+        if buffer.len() >= MAX_HEADER_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Request header too large"));
+        }
+
+        let read = stream.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+
+    Ok(String::from_utf8_lossy(&buffer).into_owned())
+}
 
-    let buf_str = String::from_utf8_lossy(&buffer);
+fn handle_client(mut stream: TcpStream, config: &ServerConfig) -> io::Result<()> {
+    let buf_str = read_request_headers(&mut stream)?;
     let request = request::HttpRequest::new(&buf_str)?;
-    let response = request.response()?;
+    let response = request.response(config)?;
 
     debug!("{:?}", response);
-    debug!("{}", String::from_utf8_lossy(&response.response_body));
 
-    let headers = format!(
-        "{} {}\r\nContent-Length: {}\r\nContent-Type: {}\r\n\r\n",
-        response.version, response.status, response.content_length, response.content_type
-    );
+    let mut headers = format!("{} {}\r\n", response.version, response.status);
+    if response.status != ResponseStatus::NotModified {
+        headers.push_str(&format!("Content-Length: {}\r\n", response.content_length));
+    }
+    headers.push_str(&format!("Content-Type: {}\r\n", response.content_type));
+    if let AcceptRanges::Bytes = response.accept_ranges {
+        headers.push_str(&format!("Accept-Ranges: {}\r\n", response.accept_ranges));
+    }
+    if let Some(content_range) = &response.content_range {
+        headers.push_str(&format!("Content-Range: {}\r\n", content_range));
+    }
+    if let Some(last_modified) = &response.last_modified {
+        headers.push_str(&format!("Last-Modified: {}\r\n", last_modified));
+    }
+    if let Some(etag) = &response.etag {
+        headers.push_str(&format!("ETag: {}\r\n", etag));
+    }
+    if let Some(allow) = &response.allow {
+        headers.push_str(&format!("Allow: {}\r\n", allow));
+    }
+    if let Some(content_encoding) = &response.content_encoding {
+        headers.push_str(&format!("Content-Encoding: {}\r\n", content_encoding));
+    }
+    if let Some(vary) = &response.vary {
+        headers.push_str(&format!("Vary: {}\r\n", vary));
+    }
+    headers.push_str("\r\n");
 
     stream.write_all(headers.as_bytes())?;
-    stream.write_all(&response.response_body)?;
+
+    match response.response_body {
+        ResponseBody::Memory(bytes) => stream.write_all(&bytes)?,
+        ResponseBody::File { mut file, start, length } => {
+            file.seek(SeekFrom::Start(start))?;
+
+            let mut remaining = length;
+            let mut chunk = [0u8; CHUNK_SIZE];
+            while remaining > 0 {
+                let want = remaining.min(CHUNK_SIZE as u64) as usize;
+                let read = file.read(&mut chunk[..want])?;
+                if read == 0 {
+                    break;
+                }
+                stream.write_all(&chunk[..read])?;
+                remaining -= read as u64;
+            }
+        }
+    }
+
     stream.flush()?;
 
     Ok(())
 }
 
-fn server(socket: SocketAddr) -> io::Result<()> {
+fn server(socket: SocketAddr, config: Arc<ServerConfig>) -> io::Result<()> {
     let listener = TcpListener::bind(socket)?;
     let pool = ThreadPool::new(4);
     let counter = Arc::new(std::sync::Mutex::new(0));
@@ -42,9 +112,10 @@ fn server(socket: SocketAddr) -> io::Result<()> {
     for stream in listener.incoming() {
         let stream = stream?;
         let counter = Arc::clone(&counter);
+        let config = Arc::clone(&config);
 
         pool.execute(move || {
-            if let Err(e) = handle_client(stream) {
+            if let Err(e) = handle_client(stream, &config) {
                 error!("Failed to handle client: {}", e);
             } else {
                 let mut counter = counter.lock().unwrap();
@@ -58,7 +129,9 @@ fn server(socket: SocketAddr) -> io::Result<()> {
 
 fn main() -> io::Result<()> {
     env_logger::init(); // Initialize the logger
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = Arc::new(ServerConfig::from_env(&args)?);
     let socket = create_socket();
-    server(socket)?;
+    server(socket, config)?;
     Ok(())
 }
\ No newline at end of file